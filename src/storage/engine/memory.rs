@@ -0,0 +1,178 @@
+// Copyright 2016 PingCAP, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::ops::Bound;
+use std::sync::{Arc, RwLock};
+
+use super::{CFS, CfName, Callback, Cursor, Engine, IterOption, Modify, OwnedModify, Result, Snapshot, WriteOptions};
+use super::worker::Worker;
+
+type Cf = BTreeMap<Vec<u8>, Vec<u8>>;
+
+pub struct EngineBtree {
+    cfs: Arc<RwLock<HashMap<CfName, Cf>>>,
+    worker: Worker,
+}
+
+impl EngineBtree {
+    pub fn new() -> EngineBtree {
+        let mut init = HashMap::new();
+        for cf in CFS {
+            init.insert(*cf, BTreeMap::new());
+        }
+        let cfs = Arc::new(RwLock::new(init));
+
+        let write_cfs = cfs.clone();
+        let write = move |_: WriteOptions, batch: Vec<OwnedModify>| -> Result<()> {
+            let mut cfs = write_cfs.write().unwrap();
+            for rev in batch {
+                match rev {
+                    OwnedModify::Delete(cf, k) => {
+                        cfs.get_mut(&cf).unwrap().remove(&k);
+                    }
+                    OwnedModify::Put(cf, k, v) => {
+                        cfs.get_mut(&cf).unwrap().insert(k, v);
+                    }
+                }
+            }
+            Ok(())
+        };
+
+        let snapshot_cfs = cfs.clone();
+        let snapshot = move || -> Result<Box<Snapshot>> {
+            // `HashMap`/`BTreeMap::clone` copy the trees rather than sharing
+            // them, so the resulting snapshot is untouched by any `write`
+            // performed after this call returns.
+            let cfs = snapshot_cfs.read().unwrap();
+            Ok(Box::new(BtreeSnapshot { cfs: cfs.clone() }) as Box<Snapshot>)
+        };
+
+        EngineBtree {
+            cfs: cfs,
+            worker: Worker::new("memory", write, snapshot),
+        }
+    }
+}
+
+impl Engine for EngineBtree {
+    fn get_cf(&self, cf: CfName, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        let cfs = self.cfs.read().unwrap();
+        Ok(cfs[&cf].get(key).cloned())
+    }
+
+    fn seek_cf(&self, cf: CfName, key: &[u8]) -> Result<Option<(Vec<u8>, Vec<u8>)>> {
+        let cfs = self.cfs.read().unwrap();
+        Ok(cfs[&cf].range(key.to_vec()..).next().map(|(k, v)| (k.clone(), v.clone())))
+    }
+
+    fn async_write(&self, opts: WriteOptions, batch: Vec<Modify>, callback: Callback<()>) -> Result<()> {
+        let owned = batch.into_iter().map(OwnedModify::from).collect();
+        self.worker.async_write(opts, owned, callback)
+    }
+
+    fn async_snapshot(&self, callback: Callback<Box<Snapshot>>) -> Result<()> {
+        self.worker.async_snapshot(callback)
+    }
+
+    fn iter_opt(&self, opts: IterOption) -> Result<Box<Cursor>> {
+        let cfs = self.cfs.read().unwrap();
+        let (lower, upper) = bounds(&opts);
+        let items: Cf = cfs[&opts.cf].range((lower, upper)).map(|(k, v)| (k.clone(), v.clone())).collect();
+        Ok(Box::new(BtreeCursor {
+            items: items,
+            cur: None,
+        }))
+    }
+}
+
+fn bounds(opts: &IterOption) -> (Bound<Vec<u8>>, Bound<Vec<u8>>) {
+    let lower = match opts.lower_bound {
+        Some(ref k) => Bound::Included(k.clone()),
+        None => Bound::Unbounded,
+    };
+    let upper = match opts.upper_bound {
+        Some(ref k) => Bound::Excluded(k.clone()),
+        None => Bound::Unbounded,
+    };
+    (lower, upper)
+}
+
+/// A `Cursor` over a copy of the bounded range, taken when the cursor is
+/// created (a linear copy); later writes are not seen through it. Seeking
+/// and stepping use `BTreeMap::range` on that copy, so each costs
+/// `O(log n)`.
+struct BtreeCursor {
+    items: Cf,
+    cur: Option<Vec<u8>>,
+}
+
+impl BtreeCursor {
+    fn set_cur(&mut self, cur: Option<Vec<u8>>) -> bool {
+        self.cur = cur;
+        self.cur.is_some()
+    }
+}
+
+impl Cursor for BtreeCursor {
+    fn seek(&mut self, key: &[u8]) -> Result<bool> {
+        let cur = self.items.range(key.to_vec()..).next().map(|(k, _)| k.clone());
+        Ok(self.set_cur(cur))
+    }
+
+    fn seek_for_prev(&mut self, key: &[u8]) -> Result<bool> {
+        let cur = self.items.range(..=key.to_vec()).next_back().map(|(k, _)| k.clone());
+        Ok(self.set_cur(cur))
+    }
+
+    fn next(&mut self) -> bool {
+        let cur = self.cur
+            .clone()
+            .and_then(|cur| self.items.range((Bound::Excluded(cur), Bound::Unbounded)).next().map(|(k, _)| k.clone()));
+        self.set_cur(cur)
+    }
+
+    fn prev(&mut self) -> bool {
+        let cur = self.cur
+            .clone()
+            .and_then(|cur| self.items.range((Bound::Unbounded, Bound::Excluded(cur))).next_back().map(|(k, _)| k.clone()));
+        self.set_cur(cur)
+    }
+
+    fn valid(&self) -> bool {
+        self.cur.is_some()
+    }
+
+    fn key(&self) -> &[u8] {
+        self.cur.as_ref().unwrap()
+    }
+
+    fn value(&self) -> &[u8] {
+        &self.items[self.cur.as_ref().unwrap()]
+    }
+}
+
+struct BtreeSnapshot {
+    cfs: HashMap<CfName, Cf>,
+}
+
+impl Snapshot for BtreeSnapshot {
+    fn get_cf(&self, cf: CfName, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        Ok(self.cfs[&cf].get(key).cloned())
+    }
+
+    fn seek_cf(&self, cf: CfName, key: &[u8]) -> Result<Option<(Vec<u8>, Vec<u8>)>> {
+        Ok(self.cfs[&cf].range(key.to_vec()..).next().map(|(k, v)| (k.clone(), v.clone())))
+    }
+}
@@ -1,31 +1,178 @@
 use self::memory::EngineBtree;
 use std::{error, result};
 use std::fmt::{self, Display, Formatter};
+use std::sync::mpsc;
 use self::rocksdb::EngineRocksdb;
 
 mod memory;
 mod rocksdb;
+mod worker;
+
+/// The column family a read or write targets. MVCC keeps its data, locks and
+/// commit records in separate keyspaces, so every read/write path is
+/// parameterized over one of these instead of assuming a single flat
+/// keyspace.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CfName {
+    Default,
+    Lock,
+    Write,
+}
+
+pub const CFS: &'static [CfName] = &[CfName::Default, CfName::Lock, CfName::Write];
+
+impl CfName {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            CfName::Default => "default",
+            CfName::Lock => "lock",
+            CfName::Write => "write",
+        }
+    }
+}
 
 #[derive(Debug)]
 pub enum Modify<'a> {
-    Delete(&'a [u8]),
-    Put((&'a [u8], &'a [u8])),
+    Delete(CfName, &'a [u8]),
+    Put(CfName, &'a [u8], &'a [u8]),
+}
+
+/// An owned counterpart of `Modify`, used once a write has been handed off
+/// to the worker thread and can no longer borrow from the caller's stack.
+#[derive(Debug, Clone)]
+pub enum OwnedModify {
+    Delete(CfName, Vec<u8>),
+    Put(CfName, Vec<u8>, Vec<u8>),
+}
+
+impl<'a> From<Modify<'a>> for OwnedModify {
+    fn from(m: Modify<'a>) -> OwnedModify {
+        match m {
+            Modify::Delete(cf, k) => OwnedModify::Delete(cf, k.to_vec()),
+            Modify::Put(cf, k, v) => OwnedModify::Put(cf, k.to_vec(), v.to_vec()),
+        }
+    }
+}
+
+/// Invoked by the worker thread with the outcome of an `async_write` or
+/// `async_snapshot` request, off the caller's hot path.
+pub type Callback<T> = Box<FnOnce(Result<T>) + Send>;
+
+/// Durability knobs for a single `write`. The defaults favour throughput:
+/// the write goes through RocksDB's WAL but does not wait for it to be
+/// fsynced, so callers that need a guaranteed-flushed commit must opt in
+/// explicitly via `sync`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WriteOptions {
+    pub sync: bool,
+    pub disable_wal: bool,
 }
 
 pub trait Engine {
-    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>>;
-    fn seek(&self, key: &[u8]) -> Result<Option<(Vec<u8>, Vec<u8>)>>;
-    fn write(&mut self, batch: Vec<Modify>) -> Result<()>;
+    fn get_cf(&self, cf: CfName, key: &[u8]) -> Result<Option<Vec<u8>>>;
+    fn seek_cf(&self, cf: CfName, key: &[u8]) -> Result<Option<(Vec<u8>, Vec<u8>)>>;
+    fn async_write(&self, opts: WriteOptions, batch: Vec<Modify>, callback: Callback<()>) -> Result<()>;
+    fn async_snapshot(&self, callback: Callback<Box<Snapshot>>) -> Result<()>;
+    fn iter_opt(&self, opts: IterOption) -> Result<Box<Cursor>>;
+
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        self.get_cf(CfName::Default, key)
+    }
+
+    fn seek(&self, key: &[u8]) -> Result<Option<(Vec<u8>, Vec<u8>)>> {
+        self.seek_cf(CfName::Default, key)
+    }
+
+    fn write(&self, opts: &WriteOptions, batch: Vec<Modify>) -> Result<()> {
+        let (tx, rx) = mpsc::channel();
+        try!(self.async_write(*opts, batch, Box::new(move |res| {
+            let _ = tx.send(res);
+        })));
+        rx.recv().unwrap_or_else(|_| Err(Error::Other("engine worker stopped".into())))
+    }
+
+    fn snapshot(&self) -> Result<Box<Snapshot>> {
+        let (tx, rx) = mpsc::channel();
+        try!(self.async_snapshot(Box::new(move |res| {
+            let _ = tx.send(res);
+        })));
+        rx.recv().unwrap_or_else(|_| Err(Error::Other("engine worker stopped".into())))
+    }
+
+    fn put(&self, key: &[u8], value: &[u8]) -> Result<()> {
+        self.write(&WriteOptions::default(), vec![Modify::Put(CfName::Default, key, value)])
+    }
+
+    fn delete(&self, key: &[u8]) -> Result<()> {
+        self.write(&WriteOptions::default(), vec![Modify::Delete(CfName::Default, key)])
+    }
+
+    fn iter(&self) -> Result<Box<Cursor>> {
+        self.iter_opt(IterOption::default())
+    }
+}
+
+/// A frozen view of the keyspace taken at the time `Engine::snapshot` was
+/// called. Writes applied to the engine afterwards must not be visible
+/// through it.
+pub trait Snapshot: Send {
+    fn get_cf(&self, cf: CfName, key: &[u8]) -> Result<Option<Vec<u8>>>;
+    fn seek_cf(&self, cf: CfName, key: &[u8]) -> Result<Option<(Vec<u8>, Vec<u8>)>>;
+
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        self.get_cf(CfName::Default, key)
+    }
 
-    fn put(&mut self, key: &[u8], value: &[u8]) -> Result<()> {
-        self.write(vec![Modify::Put((key, value))])
+    fn seek(&self, key: &[u8]) -> Result<Option<(Vec<u8>, Vec<u8>)>> {
+        self.seek_cf(CfName::Default, key)
     }
+}
 
-    fn delete(&mut self, key: &[u8]) -> Result<()> {
-        self.write(vec![Modify::Delete(key)])
+/// Bounds restricting a `Cursor` to a sub-range of a single column family's
+/// keyspace, so a scan stops at the boundary instead of running the
+/// iterator past it.
+#[derive(Clone)]
+pub struct IterOption {
+    pub cf: CfName,
+    pub lower_bound: Option<Vec<u8>>,
+    pub upper_bound: Option<Vec<u8>>,
+}
+
+impl Default for IterOption {
+    fn default() -> IterOption {
+        IterOption {
+            cf: CfName::Default,
+            lower_bound: None,
+            upper_bound: None,
+        }
+    }
+}
+
+impl IterOption {
+    pub fn new(cf: CfName, lower_bound: Option<Vec<u8>>, upper_bound: Option<Vec<u8>>) -> IterOption {
+        IterOption {
+            cf: cf,
+            lower_bound: lower_bound,
+            upper_bound: upper_bound,
+        }
     }
 }
 
+/// A bidirectional cursor over a (possibly bounded) range of the keyspace.
+///
+/// Unlike `Engine::seek`, a `Cursor` is positioned once and then walked with
+/// `next`/`prev`, so scanning a range costs one descent instead of one per
+/// key.
+pub trait Cursor {
+    fn seek(&mut self, key: &[u8]) -> Result<bool>;
+    fn seek_for_prev(&mut self, key: &[u8]) -> Result<bool>;
+    fn next(&mut self) -> bool;
+    fn prev(&mut self) -> bool;
+    fn valid(&self) -> bool;
+    fn key(&self) -> &[u8];
+    fn value(&self) -> &[u8];
+}
+
 #[derive(Debug)]
 pub enum Dsn<'a> {
     Memory,
@@ -72,22 +219,30 @@ pub type Result<T> = result::Result<T, Error>;
 
 #[cfg(test)]
 mod tests {
-    use super::{Dsn, Engine, Modify};
+    use super::{CfName, Cursor, Dsn, Engine, Modify, WriteOptions};
 
     #[test]
     fn memory() {
-        let mut e = super::new_engine(Dsn::Memory).unwrap();
-        get_put(e.as_mut());
-        batch(e.as_mut());
-        seek(e.as_mut());
+        let e = super::new_engine(Dsn::Memory).unwrap();
+        get_put(e.as_ref());
+        batch(e.as_ref());
+        seek(e.as_ref());
+        snapshot(e.as_ref());
+        cursor(e.as_ref());
+        cfs(e.as_ref());
+        write_opts(e.as_ref());
     }
 
     #[test]
     fn rocksdb() {
-        let mut e = super::new_engine(Dsn::RocksDBPath("/tmp/rocks")).unwrap();
-        get_put(e.as_mut());
-        batch(e.as_mut());
-        seek(e.as_mut());
+        let e = super::new_engine(Dsn::RocksDBPath("/tmp/rocks")).unwrap();
+        get_put(e.as_ref());
+        batch(e.as_ref());
+        seek(e.as_ref());
+        snapshot(e.as_ref());
+        cursor(e.as_ref());
+        cfs(e.as_ref());
+        write_opts(e.as_ref());
     }
 
     fn assert_has<T: Engine + ?Sized>(engine: &T, key: &[u8], value: &[u8]) {
@@ -104,7 +259,7 @@ mod tests {
         assert_eq!(v, pair.1);
     }
 
-    fn get_put<T: Engine + ?Sized>(engine: &mut T) {
+    fn get_put<T: Engine + ?Sized>(engine: &T) {
         assert_none(engine, b"x");
         engine.put(b"x", b"1").unwrap();
         assert_has(engine, b"x", b"1");
@@ -114,17 +269,22 @@ mod tests {
         assert_none(engine, b"x");
     }
 
-    fn batch<T: Engine + ?Sized>(engine: &mut T) {
-        engine.write(vec![Modify::Put((b"x", b"1")), Modify::Put((b"y", b"2"))]).unwrap();
+    fn batch<T: Engine + ?Sized>(engine: &T) {
+        engine.write(&WriteOptions::default(),
+                     vec![Modify::Put(CfName::Default, b"x", b"1"),
+                          Modify::Put(CfName::Default, b"y", b"2")])
+            .unwrap();
         assert_has(engine, b"x", b"1");
         assert_has(engine, b"y", b"2");
 
-        engine.write(vec![Modify::Delete(b"x"), Modify::Delete(b"y")]).unwrap();
+        engine.write(&WriteOptions::default(),
+                     vec![Modify::Delete(CfName::Default, b"x"), Modify::Delete(CfName::Default, b"y")])
+            .unwrap();
         assert_none(engine, b"y");
         assert_none(engine, b"y");
     }
 
-    fn seek<T: Engine + ?Sized>(engine: &mut T) {
+    fn seek<T: Engine + ?Sized>(engine: &T) {
         engine.put(b"x", b"1").unwrap();
         assert_seek(engine, b"x", (b"x", b"1"));
         assert_seek(engine, b"a", (b"x", b"1"));
@@ -135,4 +295,75 @@ mod tests {
         engine.delete(b"x").unwrap();
         engine.delete(b"z").unwrap();
     }
+
+    fn snapshot<T: Engine + ?Sized>(engine: &T) {
+        engine.put(b"x", b"1").unwrap();
+        let snap = engine.snapshot().unwrap();
+        assert_eq!(snap.get(b"x").unwrap().unwrap(), b"1");
+
+        engine.put(b"x", b"2").unwrap();
+        engine.put(b"y", b"3").unwrap();
+
+        // The snapshot must not observe writes made after it was taken.
+        assert_eq!(snap.get(b"x").unwrap().unwrap(), b"1");
+        assert_eq!(snap.get(b"y").unwrap(), None);
+
+        engine.delete(b"x").unwrap();
+        engine.delete(b"y").unwrap();
+    }
+
+    fn cursor<T: Engine + ?Sized>(engine: &T) {
+        engine.put(b"a", b"1").unwrap();
+        engine.put(b"b", b"2").unwrap();
+        engine.put(b"c", b"3").unwrap();
+
+        let mut cur = engine.iter().unwrap();
+        assert!(cur.seek(b"b").unwrap());
+        assert_eq!(cur.key(), b"b");
+        assert_eq!(cur.value(), b"2");
+
+        assert!(cur.next());
+        assert_eq!(cur.key(), b"c");
+        assert!(!cur.next());
+        assert!(!cur.valid());
+
+        assert!(cur.seek_for_prev(b"bb").unwrap());
+        assert_eq!(cur.key(), b"b");
+        assert!(cur.prev());
+        assert_eq!(cur.key(), b"a");
+        assert!(!cur.prev());
+        assert!(!cur.valid());
+
+        engine.delete(b"a").unwrap();
+        engine.delete(b"b").unwrap();
+        engine.delete(b"c").unwrap();
+    }
+
+    fn cfs<T: Engine + ?Sized>(engine: &T) {
+        engine.write(&WriteOptions::default(),
+                     vec![Modify::Put(CfName::Default, b"x", b"default"),
+                          Modify::Put(CfName::Lock, b"x", b"lock"),
+                          Modify::Put(CfName::Write, b"x", b"write")])
+            .unwrap();
+
+        assert_eq!(engine.get_cf(CfName::Default, b"x").unwrap().unwrap(), b"default");
+        assert_eq!(engine.get_cf(CfName::Lock, b"x").unwrap().unwrap(), b"lock");
+        assert_eq!(engine.get_cf(CfName::Write, b"x").unwrap().unwrap(), b"write");
+
+        engine.write(&WriteOptions::default(),
+                     vec![Modify::Delete(CfName::Default, b"x"),
+                          Modify::Delete(CfName::Lock, b"x"),
+                          Modify::Delete(CfName::Write, b"x")])
+            .unwrap();
+    }
+
+    fn write_opts<T: Engine + ?Sized>(engine: &T) {
+        let sync = WriteOptions { sync: true, ..WriteOptions::default() };
+        engine.write(&sync, vec![Modify::Put(CfName::Default, b"x", b"1")]).unwrap();
+        assert_has(engine, b"x", b"1");
+
+        let no_wal = WriteOptions { disable_wal: true, ..WriteOptions::default() };
+        engine.write(&no_wal, vec![Modify::Delete(CfName::Default, b"x")]).unwrap();
+        assert_none(engine, b"x");
+    }
 }
\ No newline at end of file
@@ -0,0 +1,222 @@
+// Copyright 2016 PingCAP, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::io;
+use std::sync::Arc;
+
+use rocksdb::{DB, Options, Writable, WriteBatch, WriteOptions as RocksWriteOptions, ReadOptions, SeekKey,
+              ColumnFamily};
+
+use super::{CFS, CfName, Callback, Cursor, Engine, Error, IterOption, Modify, OwnedModify, Result, Snapshot,
+            WriteOptions};
+use super::worker::Worker;
+
+pub struct EngineRocksdb {
+    db: Arc<DB>,
+    worker: Worker,
+}
+
+fn cf_handle(db: &DB, cf: CfName) -> Result<ColumnFamily> {
+    db.cf_handle(cf.as_str())
+        .ok_or_else(|| Error::Other(io::Error::new(io::ErrorKind::NotFound, cf.as_str()).into()))
+}
+
+impl EngineRocksdb {
+    pub fn new(path: &str) -> Result<EngineRocksdb> {
+        let cf_names: Vec<&str> = CFS.iter().map(CfName::as_str).collect();
+        let cf_opts: Vec<Options> = cf_names.iter().map(|_| Options::new()).collect();
+        let db = try!(DB::open_cf(Options::new(), path, &cf_names, &cf_opts)
+            .map(Arc::new)
+            .map_err(|e| Error::Other(e.into())));
+
+        let write_db = db.clone();
+        let write = move |opts: WriteOptions, batch: Vec<OwnedModify>| -> Result<()> {
+            let wb = WriteBatch::new();
+            for rev in batch {
+                let res = match rev {
+                    OwnedModify::Delete(cf, k) => {
+                        let handle = try!(cf_handle(&write_db, cf));
+                        wb.delete_cf(handle, &k)
+                    }
+                    OwnedModify::Put(cf, k, v) => {
+                        let handle = try!(cf_handle(&write_db, cf));
+                        wb.put_cf(handle, &k, &v)
+                    }
+                };
+                if let Err(e) = res {
+                    return Err(Error::Other(e.into()));
+                }
+            }
+            let mut wopts = RocksWriteOptions::new();
+            wopts.set_sync(opts.sync);
+            wopts.disable_wal(opts.disable_wal);
+            write_db.write_opt(wb, &wopts).map_err(|e| Error::Other(e.into()))
+        };
+
+        let snapshot_db = db.clone();
+        let snapshot = move || -> Result<Box<Snapshot>> {
+            Ok(Box::new(RocksSnapshot::new(snapshot_db.clone())) as Box<Snapshot>)
+        };
+
+        Ok(EngineRocksdb {
+            db: db,
+            worker: Worker::new("rocksdb", write, snapshot),
+        })
+    }
+
+    fn cf_handle(&self, cf: CfName) -> Result<ColumnFamily> {
+        cf_handle(&self.db, cf)
+    }
+}
+
+impl Engine for EngineRocksdb {
+    fn get_cf(&self, cf: CfName, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        let handle = try!(self.cf_handle(cf));
+        self.db.get_cf(handle, key).map(|v| v.map(|v| v.to_vec())).map_err(|e| Error::Other(e.into()))
+    }
+
+    fn seek_cf(&self, cf: CfName, key: &[u8]) -> Result<Option<(Vec<u8>, Vec<u8>)>> {
+        let handle = try!(self.cf_handle(cf));
+        let mut iter = self.db.iter_cf(handle);
+        iter.seek(key.into());
+        Ok(iter.next().map(|(k, v)| (k.to_vec(), v.to_vec())))
+    }
+
+    fn async_write(&self, opts: WriteOptions, batch: Vec<Modify>, callback: Callback<()>) -> Result<()> {
+        let owned = batch.into_iter().map(OwnedModify::from).collect();
+        self.worker.async_write(opts, owned, callback)
+    }
+
+    fn async_snapshot(&self, callback: Callback<Box<Snapshot>>) -> Result<()> {
+        self.worker.async_snapshot(callback)
+    }
+
+    fn iter_opt(&self, opts: IterOption) -> Result<Box<Cursor>> {
+        let handle = try!(self.cf_handle(opts.cf));
+        let mut ropts = ReadOptions::new();
+        if let Some(ref upper) = opts.upper_bound {
+            ropts.set_iterate_upper_bound(upper);
+        }
+        let iter = self.db.iter_cf_opt(handle, ropts);
+        Ok(Box::new(RocksCursor {
+            iter: iter,
+            lower_bound: opts.lower_bound,
+        }))
+    }
+}
+
+/// A `Cursor` backed by RocksDB's native, bidirectional `DBIterator`.
+///
+/// `DBIterator` has no notion of a lower bound, so one is enforced here by
+/// clamping `seek` targets and by invalidating the cursor if `seek_for_prev`
+/// or `prev` lands below it.
+struct RocksCursor {
+    iter: ::rocksdb::DBIterator,
+    lower_bound: Option<Vec<u8>>,
+}
+
+impl RocksCursor {
+    fn below_lower_bound(&self) -> bool {
+        match self.lower_bound {
+            Some(ref lower) => self.iter.key() < lower.as_slice(),
+            None => false,
+        }
+    }
+}
+
+impl Cursor for RocksCursor {
+    fn seek(&mut self, key: &[u8]) -> Result<bool> {
+        let key = match self.lower_bound {
+            Some(ref lower) if lower.as_slice() > key => lower.as_slice(),
+            _ => key,
+        };
+        Ok(self.iter.seek(SeekKey::Key(key)))
+    }
+
+    fn seek_for_prev(&mut self, key: &[u8]) -> Result<bool> {
+        Ok(self.iter.seek_for_prev(SeekKey::Key(key)) && !self.below_lower_bound())
+    }
+
+    fn next(&mut self) -> bool {
+        self.iter.next()
+    }
+
+    fn prev(&mut self) -> bool {
+        self.iter.prev() && !self.below_lower_bound()
+    }
+
+    fn valid(&self) -> bool {
+        self.iter.valid() && !self.below_lower_bound()
+    }
+
+    fn key(&self) -> &[u8] {
+        self.iter.key()
+    }
+
+    fn value(&self) -> &[u8] {
+        self.iter.value()
+    }
+}
+
+/// A `Snapshot` backed by a RocksDB snapshot handle.
+///
+/// `rocksdb::Snapshot<'a>` borrows the `DB` it was taken from, which makes it
+/// impossible to hand one back as an owned `Box<Snapshot>`. We erase that
+/// borrow to `'static` and instead keep the owning `Arc<DB>` alive for as
+/// long as the snapshot itself, so the handle stays valid until this struct
+/// is dropped.
+struct RocksSnapshot {
+    // `snap` must be declared before `db`: Rust drops struct fields in
+    // declaration order, and `snap`'s `Drop` calls `release_snapshot`
+    // through the transmuted `'static` reference into `db`. If `db`
+    // dropped first (and this snapshot held the last `Arc`), that would
+    // be a use-after-free. Keep this order.
+    snap: rocksdb::Snapshot<'static>,
+    db: Arc<DB>,
+}
+
+// The transmuted `'static` snapshot only ever borrows `self.db`, which this
+// struct keeps alive for exactly as long (see field order above), so moving
+// it across threads is safe even though the compiler can no longer see the
+// real borrow.
+unsafe impl Send for RocksSnapshot {}
+
+impl RocksSnapshot {
+    fn new(db: Arc<DB>) -> RocksSnapshot {
+        let snap = unsafe { ::std::mem::transmute(db.snapshot()) };
+        RocksSnapshot { db: db, snap: snap }
+    }
+
+    fn read_opts(&self) -> ReadOptions {
+        let mut opts = ReadOptions::new();
+        opts.set_snapshot(&self.snap);
+        opts
+    }
+}
+
+impl Snapshot for RocksSnapshot {
+    fn get_cf(&self, cf: CfName, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        let handle = try!(cf_handle(&self.db, cf));
+        self.db
+            .get_cf_opt(handle, key, &self.read_opts())
+            .map(|v| v.map(|v| v.to_vec()))
+            .map_err(|e| Error::Other(e.into()))
+    }
+
+    fn seek_cf(&self, cf: CfName, key: &[u8]) -> Result<Option<(Vec<u8>, Vec<u8>)>> {
+        let handle = try!(cf_handle(&self.db, cf));
+        let mut iter = self.db.iter_cf_opt(handle, self.read_opts());
+        iter.seek(key.into());
+        Ok(iter.next().map(|(k, v)| (k.to_vec(), v.to_vec())))
+    }
+}
@@ -0,0 +1,84 @@
+// Copyright 2016 PingCAP, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+
+use util::worker::metrics::PENDING_TASKS;
+
+use super::{Callback, Error, OwnedModify, Result, Snapshot, WriteOptions};
+
+enum Task {
+    Write(WriteOptions, Vec<OwnedModify>, Callback<()>),
+    Snapshot(Callback<Box<Snapshot>>),
+}
+
+/// Decrements `PENDING_TASKS` for `name` when dropped, so a task is counted
+/// as pending until it finishes (or panics), not merely until it's dequeued.
+struct PendingTaskGuard {
+    name: &'static str,
+}
+
+impl Drop for PendingTaskGuard {
+    fn drop(&mut self) {
+        PENDING_TASKS.with_label_values(&[self.name]).dec();
+    }
+}
+
+/// Runs writes and snapshots on a dedicated background thread so
+/// `Engine::async_write`/`async_snapshot` never block the caller on the
+/// backend's slow path. `PENDING_TASKS` tracks how many requests are queued
+/// but not yet applied, so operators can see buildup in the worker.
+pub struct Worker {
+    name: &'static str,
+    tx: Sender<Task>,
+}
+
+impl Worker {
+    pub fn new<W, S>(name: &'static str, mut write: W, snapshot: S) -> Worker
+        where W: FnMut(WriteOptions, Vec<OwnedModify>) -> Result<()> + Send + 'static,
+              S: Fn() -> Result<Box<Snapshot>> + Send + 'static
+    {
+        let (tx, rx) = mpsc::channel();
+        thread::Builder::new()
+            .name(format!("engine-worker-{}", name))
+            .spawn(move || {
+                for task in rx {
+                    let _guard = PendingTaskGuard { name: name };
+                    match task {
+                        Task::Write(opts, batch, callback) => callback(write(opts, batch)),
+                        Task::Snapshot(callback) => callback(snapshot()),
+                    }
+                }
+            })
+            .unwrap();
+        Worker { name: name, tx: tx }
+    }
+
+    pub fn async_write(&self, opts: WriteOptions, batch: Vec<OwnedModify>, callback: Callback<()>) -> Result<()> {
+        self.send(Task::Write(opts, batch, callback))
+    }
+
+    pub fn async_snapshot(&self, callback: Callback<Box<Snapshot>>) -> Result<()> {
+        self.send(Task::Snapshot(callback))
+    }
+
+    fn send(&self, task: Task) -> Result<()> {
+        PENDING_TASKS.with_label_values(&[self.name]).inc();
+        self.tx.send(task).map_err(|_| {
+            // The task was never queued, so it must not stay counted.
+            PENDING_TASKS.with_label_values(&[self.name]).dec();
+            Error::Other("engine worker stopped".into())
+        })
+    }
+}